@@ -0,0 +1,27 @@
+use crate::{Client, ClientError};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::RequestInit;
+
+pub(crate) async fn fetch_with_request(
+    request: web_sys::Request,
+) -> Result<web_sys::Response, ClientError> {
+    let window = web_sys::window()
+        .ok_or_else(|| ClientError::Other("Could not get browser window".into()))?;
+
+    let fetch = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(ClientError::FetchFailed)?;
+
+    fetch.dyn_into().map_err(ClientError::FetchFailed)
+}
+
+pub(crate) fn post_init(client: Client, signal: &web_sys::AbortSignal) -> RequestInit {
+    let mut init = RequestInit::new();
+    init.method("POST");
+    init.credentials(client.credentials);
+    init.mode(client.mode);
+    init.signal(Some(signal));
+
+    init
+}
@@ -0,0 +1,386 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::{BufMut, Bytes, BytesMut};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression as GzLevel};
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+use http_body::Body;
+use std::{
+    io::{Read, Write},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tonic::{Code, Status};
+
+const GRPC_WEB: &str = "application/grpc-web";
+const GRPC_WEB_PROTO: &str = "application/grpc-web+proto";
+const GRPC_WEB_TEXT: &str = "application/grpc-web-text";
+const GRPC_WEB_TEXT_PROTO: &str = "application/grpc-web-text+proto";
+
+/// The wire framing used when talking to the grpc-web endpoint.
+///
+/// `None` is the raw binary `application/grpc-web` framing; `Base64` is the
+/// `application/grpc-web-text` variant required by some proxies and
+/// CSP-restricted environments, where every frame is base64 encoded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    None,
+    Base64,
+}
+
+impl Encoding {
+    pub fn from_content_type(headers: &HeaderMap) -> Encoding {
+        Self::from_header(headers.get(CONTENT_TYPE))
+    }
+
+    fn from_header(value: Option<&HeaderValue>) -> Encoding {
+        match value.and_then(|v| v.to_str().ok()) {
+            Some(GRPC_WEB_TEXT) | Some(GRPC_WEB_TEXT_PROTO) => Encoding::Base64,
+            _ => Encoding::None,
+        }
+    }
+
+    pub fn to_content_type(self) -> &'static str {
+        match self {
+            Encoding::None => GRPC_WEB_PROTO,
+            Encoding::Base64 => GRPC_WEB_TEXT_PROTO,
+        }
+    }
+}
+
+/// Per-message compression applied to frame payloads.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+}
+
+impl Compression {
+    /// The value advertised via the `grpc-encoding` header, or `None` when no
+    /// compression is in effect.
+    pub fn grpc_encoding(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gzip"),
+        }
+    }
+
+    /// Pick the compression used by a response from its `grpc-encoding` header.
+    pub fn from_headers(headers: &HeaderMap) -> Compression {
+        match headers.get("grpc-encoding").and_then(|v| v.to_str().ok()) {
+            Some("gzip") => Compression::Gzip,
+            _ => Compression::None,
+        }
+    }
+
+    fn compress(self, payload: &[u8]) -> Result<Vec<u8>, Status> {
+        match self {
+            Compression::None => Ok(payload.to_vec()),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), GzLevel::default());
+                encoder
+                    .write_all(payload)
+                    .and_then(|_| encoder.finish())
+                    .map_err(|_| Status::internal("failed to gzip message payload"))
+            }
+        }
+    }
+
+    fn decompress(self, payload: &[u8]) -> Result<Vec<u8>, Status> {
+        match self {
+            Compression::None => Ok(payload.to_vec()),
+            Compression::Gzip => {
+                let mut decoder = GzDecoder::new(payload);
+                let mut out = Vec::new();
+                decoder
+                    .read_to_end(&mut out)
+                    .map_err(|_| Status::internal("failed to gunzip message payload"))?;
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Re-frame a fully buffered request body, compressing each message payload
+/// that meets `threshold` and flagging it in the frame header. Frames smaller
+/// than the threshold are forwarded untouched, matching standard gRPC.
+pub fn compress_request(
+    body: Bytes,
+    compression: Compression,
+    threshold: usize,
+) -> Result<Bytes, Status> {
+    if compression == Compression::None {
+        return Ok(body);
+    }
+
+    let mut buf = &body[..];
+    let mut out = BytesMut::with_capacity(body.len());
+
+    while buf.len() >= HEADER_LEN {
+        let flags = buf[0];
+        let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+        if buf.len() < HEADER_LEN + len {
+            break;
+        }
+        let payload = &buf[HEADER_LEN..HEADER_LEN + len];
+
+        if flags & COMPRESSED_BIT == 0 && len >= threshold {
+            let compressed = compression.compress(payload)?;
+            out.put_u8(flags | COMPRESSED_BIT);
+            out.put_u32(compressed.len() as u32);
+            out.put_slice(&compressed);
+        } else {
+            out.put_slice(&buf[..HEADER_LEN + len]);
+        }
+
+        buf = &buf[HEADER_LEN + len..];
+    }
+
+    Ok(out.freeze())
+}
+
+/// An opt-in policy for replaying failed unary RPCs with exponential backoff.
+///
+/// A call is retried when the `fetch` rejects at the network layer or when the
+/// response carries a `grpc-status` header in
+/// [`retryable`](Self::with_retryable). A `grpc-status` delivered in the
+/// trailer frame of the response body does not trigger a retry. Backoff is
+/// capped so it never exceeds the call's remaining deadline.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub(crate) max_attempts: usize,
+    pub(crate) initial_backoff: Duration,
+    pub(crate) max_backoff: Duration,
+    pub(crate) retryable: Vec<Code>,
+}
+
+impl RetryPolicy {
+    /// A policy making at most `max_attempts` total attempts, backing off from
+    /// 100ms (doubling, capped at 5s) and retrying only `Unavailable`.
+    pub fn new(max_attempts: usize) -> Self {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            retryable: vec![Code::Unavailable],
+        }
+    }
+
+    /// Set the backoff applied before the first retry; it doubles each attempt.
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Cap the exponential backoff at `backoff`.
+    pub fn with_max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Replace the set of gRPC status codes that trigger a retry.
+    pub fn with_retryable(mut self, codes: Vec<Code>) -> Self {
+        self.retryable = codes;
+        self
+    }
+
+    pub(crate) fn is_retryable(&self, code: i32) -> bool {
+        self.retryable.iter().any(|c| *c as i32 == code)
+    }
+}
+
+/// Marks which half of the call a [`GrpcWebCall`] is transforming.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Direction {
+    Response,
+}
+
+// The leading byte of a grpc-web frame header whose high bit flags a trailer
+// frame rather than a message frame.
+const TRAILER_BIT: u8 = 0b1000_0000;
+// Bit 0 of the frame flags byte marks the payload as compressed with the
+// algorithm named by the `grpc-encoding` header.
+const COMPRESSED_BIT: u8 = 0b0000_0001;
+const HEADER_LEN: usize = 5;
+
+/// Adapts a grpc-web framed body into the grpc framing tonic expects.
+///
+/// On the response side it passes uncompressed message frames through
+/// untouched, inflates compressed ones (re-emitting them with the compressed
+/// flag cleared), and peels the trailing trailer frame off the stream,
+/// surfacing it through [`Body::poll_trailers`].
+pub struct GrpcWebCall<B> {
+    inner: B,
+    encoding: Encoding,
+    compression: Compression,
+    direction: Direction,
+    buf: BytesMut,
+    // Raw base64 characters of an incomplete quartet carried across
+    // `poll_data` calls when decoding the `application/grpc-web-text` stream.
+    base64_leftover: BytesMut,
+    trailers: Option<HeaderMap>,
+    finished: bool,
+}
+
+impl<B> GrpcWebCall<B> {
+    pub fn client_response(inner: B, encoding: Encoding, compression: Compression) -> Self {
+        GrpcWebCall {
+            inner,
+            encoding,
+            compression,
+            direction: Direction::Response,
+            buf: BytesMut::new(),
+            base64_leftover: BytesMut::new(),
+            trailers: None,
+            finished: false,
+        }
+    }
+
+    // Feed an inbound chunk into the frame buffer, base64-decoding first when
+    // the response uses the grpc-web-text encoding. Because the stream is not
+    // aligned to 4-character quartets, any trailing 1-3 characters are held in
+    // `base64_leftover` until the next chunk completes them.
+    fn absorb(&mut self, data: Bytes) -> Result<(), Status> {
+        match self.encoding {
+            Encoding::None => self.buf.put(data),
+            Encoding::Base64 => {
+                self.base64_leftover.put(data);
+                let complete = self.base64_leftover.len() - self.base64_leftover.len() % 4;
+                if complete > 0 {
+                    let quartets = self.base64_leftover.split_to(complete);
+                    let decoded = STANDARD
+                        .decode(&quartets)
+                        .map_err(|_| Status::internal("invalid base64 in grpc-web-text frame"))?;
+                    self.buf.put(Bytes::from(decoded));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Try to split a single complete frame off the front of `buf`, returning
+    // its flags byte together with the framed bytes (header included).
+    fn next_frame(&mut self) -> Option<(u8, Bytes)> {
+        if self.buf.len() < HEADER_LEN {
+            return None;
+        }
+
+        let flags = self.buf[0];
+        let len = u32::from_be_bytes([self.buf[1], self.buf[2], self.buf[3], self.buf[4]]) as usize;
+        let frame_len = HEADER_LEN + len;
+
+        if self.buf.len() < frame_len {
+            return None;
+        }
+
+        let frame = self.buf.split_to(frame_len).freeze();
+        Some((flags, frame))
+    }
+
+    // Parse a trailer frame payload (HTTP/1 style `name: value` lines) into a
+    // `HeaderMap`.
+    fn parse_trailers(payload: &[u8]) -> Result<HeaderMap, Status> {
+        let mut map = HeaderMap::new();
+
+        for line in payload.split(|&b| b == b'\n') {
+            let line = match line.strip_suffix(b"\r") {
+                Some(rest) => rest,
+                None => line,
+            };
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let idx = line
+                .iter()
+                .position(|&b| b == b':')
+                .ok_or_else(|| Status::internal("malformed grpc-web trailer frame"))?;
+
+            let name = HeaderName::from_bytes(&line[..idx])
+                .map_err(|_| Status::internal("invalid grpc-web trailer name"))?;
+            let value = HeaderValue::from_bytes(trim(&line[idx + 1..]))
+                .map_err(|_| Status::internal("invalid grpc-web trailer value"))?;
+
+            map.append(name, value);
+        }
+
+        Ok(map)
+    }
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace());
+    match start {
+        Some(start) => {
+            let end = bytes.iter().rposition(|b| !b.is_ascii_whitespace()).unwrap();
+            &bytes[start..=end]
+        }
+        None => &[],
+    }
+}
+
+impl<B> Body for GrpcWebCall<B>
+where
+    B: Body<Data = Bytes, Error = Status> + Unpin,
+{
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        debug_assert_eq!(self.direction, Direction::Response);
+
+        loop {
+            if let Some((flags, frame)) = self.next_frame() {
+                if flags & TRAILER_BIT == TRAILER_BIT {
+                    let trailers = Self::parse_trailers(&frame[HEADER_LEN..])?;
+                    self.trailers = Some(trailers);
+                    self.finished = true;
+                    continue;
+                }
+
+                // A compressed message frame is inflated with the algorithm
+                // advertised by the response's `grpc-encoding` header and
+                // re-emitted with the compressed bit cleared.
+                if flags & COMPRESSED_BIT == COMPRESSED_BIT {
+                    let payload = self.compression.decompress(&frame[HEADER_LEN..])?;
+                    let mut inflated = BytesMut::with_capacity(HEADER_LEN + payload.len());
+                    inflated.put_u8(flags & !COMPRESSED_BIT);
+                    inflated.put_u32(payload.len() as u32);
+                    inflated.put_slice(&payload);
+                    return Poll::Ready(Some(Ok(inflated.freeze())));
+                }
+
+                return Poll::Ready(Some(Ok(frame)));
+            }
+
+            if self.finished {
+                return Poll::Ready(None);
+            }
+
+            match Pin::new(&mut self.inner).poll_data(cx) {
+                Poll::Ready(Some(Ok(data))) => {
+                    self.absorb(data)?;
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => {
+                    self.finished = true;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(self.trailers.take()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.finished && self.buf.is_empty()
+    }
+}
@@ -1,4 +1,5 @@
 mod call;
+mod websocket;
 use cfg_if::cfg_if;
 
 cfg_if! {
@@ -12,12 +13,15 @@ cfg_if! {
 }
 
 use bytes::Bytes;
-use call::{Encoding, GrpcWebCall};
+use base64::{engine::general_purpose::STANDARD, Engine};
+pub use call::{Compression, Encoding, RetryPolicy};
+use call::{compress_request, GrpcWebCall};
 use core::{
     fmt,
     task::{Context, Poll},
 };
 use futures::{Future, Stream, TryStreamExt};
+use gloo_timers::callback::Timeout;
 use http::{
     header::{HeaderName, InvalidHeaderName, InvalidHeaderValue, ToStrError},
     request::Request,
@@ -26,11 +30,11 @@ use http::{
 };
 use http_body::Body;
 use js_sys::{Array, Uint8Array};
-use std::{error::Error, pin::Pin};
+use std::{cell::RefCell, error::Error, pin::Pin, rc::Rc, time::Duration};
 use tonic::{body::BoxBody, client::GrpcService, Status};
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_streams::ReadableStream;
-use web_sys::Headers;
+use web_sys::{AbortController, Headers};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ClientError {
@@ -70,16 +74,41 @@ impl fmt::Display for ClientError {
     }
 }
 
+/// An async hook invoked with the fully merged request headers just before the
+/// request is issued, letting callers refresh a bearer token or compute a
+/// signature per call.
+type Interceptor = Rc<RefCell<dyn FnMut(&mut HeaderMap) -> Pin<Box<dyn Future<Output = ()>>>>>;
+
 pub type CredentialsMode = web_sys::RequestCredentials;
 
 pub type RequestMode = web_sys::RequestMode;
 
+/// The network transport a [`Client`] uses to carry RPCs.
+///
+/// `Fetch` is the default `fetch`-based transport, which supports unary and
+/// server-streaming calls. `WebSocket` tunnels grpc-web over a WebSocket and
+/// additionally supports client-streaming and bidirectional calls, which
+/// `fetch` cannot because it cannot stream a request body.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Transport {
+    Fetch,
+    WebSocket,
+}
+
 #[derive(Clone)]
 pub struct Client {
     base_uri: String,
     credentials: CredentialsMode,
     mode: RequestMode,
     encoding: Encoding,
+    timeout: Option<Duration>,
+    compression: Compression,
+    compression_threshold: usize,
+    transport: Transport,
+    user_agent: String,
+    default_headers: HeaderMap,
+    interceptor: Option<Interceptor>,
+    retry: Option<RetryPolicy>,
 }
 
 impl Client {
@@ -89,75 +118,318 @@ impl Client {
             credentials: CredentialsMode::SameOrigin,
             mode: RequestMode::Cors,
             encoding: Encoding::None,
+            timeout: None,
+            compression: Compression::None,
+            compression_threshold: 0,
+            transport: Transport::Fetch,
+            user_agent: "grpc-web-rust/0.1".into(),
+            default_headers: HeaderMap::new(),
+            interceptor: None,
+            retry: None,
+        }
+    }
+
+    /// Replay failed RPCs according to `policy`. Retries apply to calls on the
+    /// fetch transport, whose request body is fully buffered and so can be
+    /// re-sent, and never exceed the call's deadline. Server-streaming calls
+    /// are retried as well as unary ones when the failure comes before the
+    /// response is returned; calls on the websocket transport are never
+    /// retried.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Override the `x-user-agent` header sent with every request.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Merge `name: value` into the headers of every request, e.g. a static
+    /// auth token or tracing header. Later calls override earlier ones.
+    pub fn with_default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.default_headers.insert(name, value);
+        self
+    }
+
+    /// Register an async interceptor invoked with the merged request headers
+    /// just before each request is issued, so callers can refresh a bearer
+    /// token or compute a signature without wrapping the whole service.
+    pub fn with_interceptor<F, Fut>(mut self, mut f: F) -> Self
+    where
+        F: FnMut(&mut HeaderMap) -> Fut + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        self.interceptor = Some(Rc::new(RefCell::new(move |headers: &mut HeaderMap| {
+            Box::pin(f(headers)) as Pin<Box<dyn Future<Output = ()>>>
+        })));
+        self
+    }
+
+    // Layer the configured user agent, default headers, and interceptor onto a
+    // request's headers. Called by every transport just before dispatch.
+    async fn apply_extra_headers(&self, headers: &mut HeaderMap) {
+        if let Ok(value) = HeaderValue::from_str(&self.user_agent) {
+            headers.insert(HeaderName::from_static("x-user-agent"), value);
+        }
+        for (name, value) in self.default_headers.iter() {
+            headers.insert(name.clone(), value.clone());
         }
+        if let Some(interceptor) = &self.interceptor {
+            let fut = (interceptor.borrow_mut())(headers);
+            fut.await;
+        }
+    }
+
+    /// Carry RPCs over a WebSocket instead of `fetch`, enabling
+    /// client-streaming and bidirectional calls.
+    pub fn with_websocket(mut self) -> Self {
+        self.transport = Transport::WebSocket;
+        self
+    }
+
+    /// Select the wire [`Encoding`]. Use [`Encoding::Base64`] to speak the
+    /// `application/grpc-web-text` variant required by some proxies and
+    /// CSP-restricted environments. The websocket transport ignores this
+    /// setting and always uses the binary framing.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
     }
 
-    async fn request(self, rpc: Request<BoxBody>) -> Result<Response<BoxBody>, ClientError> {
+    /// Abort the underlying `fetch` if a response has not arrived within
+    /// `timeout`. A per-call `grpc-timeout` request header, when shorter,
+    /// takes precedence over this default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Compress outgoing message payloads with the given [`Compression`] and
+    /// advertise it via the `grpc-encoding`/`grpc-accept-encoding` headers.
+    /// Messages smaller than the threshold set by
+    /// [`with_compression_threshold`](Self::with_compression_threshold) are
+    /// sent uncompressed. The websocket transport ignores this setting and
+    /// sends messages uncompressed.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Leave messages below `threshold` bytes uncompressed even when
+    /// compression is enabled, matching standard gRPC behavior.
+    pub fn with_compression_threshold(mut self, threshold: usize) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    async fn request(self, mut rpc: Request<BoxBody>) -> Result<Response<BoxBody>, ClientError> {
         let mut uri = rpc.uri().to_string();
         uri.insert_str(0, &self.base_uri);
 
+        self.apply_extra_headers(rpc.headers_mut()).await;
+
         let headers =
             Headers::new().map_err(|_| ClientError::Other("Failed to create headers".into()))?;
 
         for (k, v) in rpc.headers().iter() {
             headers.set(k.as_str(), v.to_str()?)?;
         }
-        headers.set("x-user-agent", "grpc-web-rust/0.1")?;
         headers.set("content-type", self.encoding.to_content_type())?;
 
+        let compression = self.compression;
+        let compression_threshold = self.compression_threshold;
+        if let Some(encoding) = compression.grpc_encoding() {
+            headers.set("grpc-encoding", encoding)?;
+            headers.set("grpc-accept-encoding", encoding)?;
+        }
+
+        let deadline = deadline_for(&self, rpc.headers());
+
         let body_bytes = hyper::body::to_bytes(rpc.into_body())
             .await
             .map_err(|_| ClientError::Other("Failed to convert RPC body to bytes".into()))?;
 
-        let body_array: Uint8Array = body_bytes.as_ref().into();
+        // Gzip each outgoing message frame at or over the threshold and flag it
+        // in the frame header before framing for the wire.
+        let body_bytes = compress_request(body_bytes, compression, compression_threshold)
+            .map_err(|e| ClientError::Other(format!("Failed to compress request: {}", e)))?;
+
+        // The grpc-web-text variant base64-encodes the buffered frame bytes in
+        // one pass before they are handed to fetch; the binary variant sends
+        // the frame bytes as-is. The
+        // buffered array is the "frozen" request replayed on each retry.
+        let body_array: Uint8Array = match self.encoding {
+            Encoding::Base64 => STANDARD.encode(&body_bytes).into_bytes().as_slice().into(),
+            Encoding::None => body_bytes.as_ref().into(),
+        };
         let body_js: &JsValue = body_array.as_ref();
 
-        let mut init = request::post_init(self);
-        init.body(Some(body_js)).headers(headers.as_ref());
-
-        let request = web_sys::Request::new_with_str_and_init(&uri, &init)?;
-        let fetch_res = request::fetch_with_request(request).await?;
-
-        let mut res = Response::builder().status(fetch_res.status());
-        let headers = res
-            .headers_mut()
-            .ok_or_else(|| ClientError::Other("Could not get response headers".into()))?;
-
-        for kv in js_sys::try_iter(fetch_res.headers().as_ref())?
-            .ok_or_else(|| ClientError::Other("Response headers iterator was empty".into()))?
-        {
-            let pair: Array = kv?.into();
-            headers.append(
-                HeaderName::from_bytes(
-                    pair.get(0)
-                        .as_string()
-                        .ok_or_else(|| ClientError::Other("Header pair had no name".into()))?
-                        .as_bytes(),
-                )?,
-                HeaderValue::from_str(
-                    &pair
-                        .get(1)
-                        .as_string()
-                        .ok_or_else(|| ClientError::Other("Header pair had no value".into()))?,
-                )?,
+        // The time budget left for the call; each backoff is drawn from it so a
+        // retry never runs past the deadline.
+        // It is measured against the wall clock, so time spent in failed
+        // attempts counts against it as well.
+        let started = js_sys::Date::now();
+        let budget_left = || {
+            let elapsed = Duration::from_millis((js_sys::Date::now() - started) as u64);
+            deadline.map(|d| d.saturating_sub(elapsed))
+        };
+        let mut remaining = deadline;
+        let mut attempt = 0usize;
+
+        loop {
+            attempt += 1;
+
+            // Arm an abort controller so a hung RPC does not block forever and a
+            // dropped response future tears down the underlying network request.
+            let controller = AbortController::new()
+                .map_err(|_| ClientError::Other("Failed to create AbortController".into()))?;
+            let signal = controller.signal();
+            let abort = AbortGuard::new(controller.clone());
+            let timer = remaining.map(|timeout| {
+                let controller = controller.clone();
+                Timeout::new(timeout.as_millis() as u32, move || controller.abort())
+            });
+
+            let mut init = request::post_init(self.clone(), &signal);
+            init.body(Some(body_js)).headers(headers.as_ref());
+
+            let request = web_sys::Request::new_with_str_and_init(&uri, &init)?;
+            let fetch_res = match request::fetch_with_request(request).await {
+                Ok(res) => res,
+                // A `fetch` aborted by our deadline timer rejects with an
+                // `AbortError`; surface it to tonic as `deadline_exceeded`.
+                Err(ClientError::FetchFailed(val)) if is_abort_error(&val) => {
+                    return Response::builder()
+                        .status(200)
+                        .body(errored_body(Status::deadline_exceeded(
+                            "grpc-web request deadline exceeded",
+                        )))
+                        .map_err(|e| ClientError::Other(format!("An HTTP error ocurred: {}", e)));
+                }
+                // A network-layer rejection is replayable: back off and retry
+                // the frozen request while the policy and deadline allow it.
+                Err(e) => {
+                    remaining = budget_left();
+                    match next_backoff(&self.retry, attempt, &mut remaining) {
+                        Some(delay) => {
+                            sleep(delay).await;
+                            continue;
+                        }
+                        None => return Err(e),
+                    }
+                }
+            };
+
+            let mut res = Response::builder().status(fetch_res.status());
+            let res_headers = res
+                .headers_mut()
+                .ok_or_else(|| ClientError::Other("Could not get response headers".into()))?;
+
+            for kv in js_sys::try_iter(fetch_res.headers().as_ref())?
+                .ok_or_else(|| ClientError::Other("Response headers iterator was empty".into()))?
+            {
+                let pair: Array = kv?.into();
+                res_headers.append(
+                    HeaderName::from_bytes(
+                        pair.get(0)
+                            .as_string()
+                            .ok_or_else(|| ClientError::Other("Header pair had no name".into()))?
+                            .as_bytes(),
+                    )?,
+                    HeaderValue::from_str(
+                        &pair
+                            .get(1)
+                            .as_string()
+                            .ok_or_else(|| ClientError::Other("Header pair had no value".into()))?,
+                    )?,
+                );
+            }
+
+            // A retryable `grpc-status` delivered as a (trailers-only) response
+            // header replays the call; a status carried in an in-band trailer
+            // frame surfaces on the body stream and is not re-driven.
+            if let (Some(policy), Some(code)) = (self.retry.as_ref(), grpc_status_header(res_headers))
+            {
+                if policy.is_retryable(code) {
+                    remaining = budget_left();
+                    if let Some(delay) = next_backoff(&self.retry, attempt, &mut remaining) {
+                        // Tear down this attempt's fetch before waiting.
+                        drop(timer);
+                        controller.abort();
+                        sleep(delay).await;
+                        continue;
+                    }
+                }
+            }
+
+            let body_stream = ReadableStream::from_raw(
+                fetch_res
+                    .body()
+                    .ok_or_else(|| ClientError::Other("Response body was empty".into()))?
+                    .unchecked_into(),
             );
+            let body = GrpcWebCall::client_response(
+                ReadableStreamBody::new(body_stream, Some(abort), timer),
+                Encoding::from_content_type(res_headers),
+                Compression::from_headers(res_headers),
+            );
+
+            return Ok(res
+                .body(BoxBody::new(body))
+                .map_err(|e| ClientError::Other(format!("An HTTP error ocurred: {}", e)))?);
         }
+    }
+}
+
+// The backoff before the next attempt, or `None` when no retry policy is set,
+// the attempts are exhausted, or the delay would exceed the remaining deadline.
+// Draws the returned delay from `remaining` so backoff stays within budget.
+fn next_backoff(
+    policy: &Option<RetryPolicy>,
+    attempt: usize,
+    remaining: &mut Option<Duration>,
+) -> Option<Duration> {
+    let policy = policy.as_ref()?;
+    if attempt >= policy.max_attempts {
+        return None;
+    }
 
-        let body_stream = ReadableStream::from_raw(
-            fetch_res
-                .body()
-                .ok_or_else(|| ClientError::Other("Response body was empty".into()))?
-                .unchecked_into(),
-        );
-        let body = GrpcWebCall::client_response(
-            ReadableStreamBody::new(body_stream),
-            Encoding::from_content_type(headers),
-        );
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1) as u32);
+    let delay = policy
+        .initial_backoff
+        .checked_mul(factor)
+        .unwrap_or(policy.max_backoff)
+        .min(policy.max_backoff);
 
-        Ok(res
-            .body(BoxBody::new(body))
-            .map_err(|e| ClientError::Other(format!("An HTTP error ocurred: {}", e)))?)
+    if let Some(budget) = remaining {
+        if delay >= *budget {
+            return None;
+        }
+        *budget -= delay;
     }
+
+    Some(delay)
+}
+
+// The numeric `grpc-status` carried on a response's headers, if any.
+fn grpc_status_header(headers: &HeaderMap) -> Option<i32> {
+    headers
+        .get("grpc-status")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+}
+
+// Resolve after `duration` using a browser timer, so retry backoff yields to
+// the event loop rather than spinning.
+async fn sleep(duration: Duration) {
+    let (tx, rx) = futures::channel::oneshot::channel::<()>();
+    Timeout::new(duration.as_millis() as u32, move || {
+        let _ = tx.send(());
+    })
+    .forget();
+    let _ = rx.await;
 }
 
 impl GrpcService<BoxBody> for Client {
@@ -170,17 +442,26 @@ impl GrpcService<BoxBody> for Client {
     }
 
     fn call(&mut self, rpc: Request<BoxBody>) -> Self::Future {
-        Box::pin(self.clone().request(rpc))
+        match self.transport {
+            Transport::Fetch => Box::pin(self.clone().request(rpc)),
+            Transport::WebSocket => Box::pin(websocket::request(self.clone(), rpc)),
+        }
     }
 }
 
 struct ReadableStreamBody {
     stream: Pin<Box<dyn Stream<Item = Result<Bytes, Status>>>>,
+    // Held for their drop side effects: aborting the in-flight fetch and
+    // cancelling the deadline timer once the response body is dropped.
+    _abort: Option<AbortGuard>,
+    _timer: Option<Timeout>,
 }
 
 impl ReadableStreamBody {
-    fn new(inner: ReadableStream) -> Self {
+    fn new(inner: ReadableStream, abort: Option<AbortGuard>, timer: Option<Timeout>) -> Self {
         ReadableStreamBody {
+            _abort: abort,
+            _timer: timer,
             stream: Box::pin(
                 inner
                     .into_stream()
@@ -220,6 +501,92 @@ impl Body for ReadableStreamBody {
     }
 }
 
+/// Aborts the associated `fetch` when dropped, so that a cancelled tonic
+/// future (which drops the response body) stops the underlying request.
+struct AbortGuard(AbortController);
+
+impl AbortGuard {
+    fn new(controller: AbortController) -> Self {
+        AbortGuard(controller)
+    }
+}
+
+impl Drop for AbortGuard {
+    fn drop(&mut self) {
+        // Aborting an already-completed fetch is a harmless no-op.
+        self.0.abort();
+    }
+}
+
+/// A body that fails a single time with the given [`Status`]. Used to surface
+/// a transport-level deadline as a gRPC status on the response stream.
+struct ErroredBody(Option<Status>);
+
+impl Body for ErroredBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Poll::Ready(self.0.take().map(Err))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+fn errored_body(status: Status) -> BoxBody {
+    BoxBody::new(ErroredBody(Some(status)))
+}
+
+// Is `val` a DOMException raised because the request was aborted?
+fn is_abort_error(val: &JsValue) -> bool {
+    val.dyn_ref::<web_sys::DomException>()
+        .map(|e| e.name() == "AbortError")
+        .unwrap_or(false)
+}
+
+// The effective deadline for a call: the shorter of the client default and any
+// per-call `grpc-timeout` request header set by tonic.
+fn deadline_for(client: &Client, headers: &HeaderMap) -> Option<Duration> {
+    let header = headers
+        .get("grpc-timeout")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_grpc_timeout);
+
+    match (client.timeout, header) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// Parse a gRPC `grpc-timeout` value (a decimal count followed by a unit, e.g.
+// `100m` for 100 milliseconds) into a `Duration`.
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    let (digits, unit) = value.split_at(value.len().checked_sub(1)?);
+    let amount: u64 = digits.parse().ok()?;
+    let nanos = match unit {
+        "H" => amount.checked_mul(3_600_000_000_000)?,
+        "M" => amount.checked_mul(60_000_000_000)?,
+        "S" => amount.checked_mul(1_000_000_000)?,
+        "m" => amount.checked_mul(1_000_000)?,
+        "u" => amount.checked_mul(1_000)?,
+        "n" => amount,
+        _ => return None,
+    };
+    Some(Duration::from_nanos(nanos))
+}
+
 // WARNING: these are required to satisfy the Body and Error traits, but JsValue is not thread-safe.
 // This shouldn't be an issue because wasm doesn't have threads currently.
 
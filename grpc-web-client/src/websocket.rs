@@ -0,0 +1,209 @@
+use crate::{Client, ClientError};
+use crate::call::{Compression, Encoding, GrpcWebCall};
+use bytes::{Bytes, BytesMut};
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+use futures::StreamExt;
+use http::{response::Response, HeaderMap};
+use http_body::Body;
+use js_sys::Uint8Array;
+use tonic::{body::BoxBody, Status};
+use wasm_bindgen::{closure::Closure, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+// The subprotocol understood by grpcwebproxy-style endpoints that tunnel
+// grpc-web over a WebSocket, giving the full-duplex streaming that `fetch`
+// cannot provide because it cannot stream a request body.
+const WEBSOCKET_PROTOCOL: &str = "grpc-websockets";
+
+// Leading byte of an outbound data frame: `DATA` carries a grpc-web message
+// frame, `FINISH` half-closes the client send direction.
+const FRAME_DATA: u8 = 0x00;
+const FRAME_FINISH: u8 = 0x01;
+
+/// Open a WebSocket to the base URI and speak the grpc-web-over-websocket
+/// framing, returning a response whose body yields the server's message and
+/// trailer frames. Unlike [`Client::request`], the request body is forwarded
+/// incrementally so client-streaming and bidirectional RPCs work.
+pub(crate) async fn request(
+    client: Client,
+    mut rpc: http::Request<BoxBody>,
+) -> Result<Response<BoxBody>, ClientError> {
+    client.apply_extra_headers(rpc.headers_mut()).await;
+
+    let url = websocket_url(&client.base_uri, &rpc.uri().to_string());
+
+    let socket = WebSocket::new_with_str(&url, WEBSOCKET_PROTOCOL)
+        .map_err(ClientError::FetchFailed)?;
+    socket.set_binary_type(BinaryType::Arraybuffer);
+
+    // Bridge the socket callbacks into a stream the response body drains.
+    let (tx, rx) = unbounded::<Result<Bytes, Status>>();
+
+    let on_message = {
+        let tx = tx.clone();
+        Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            if let Ok(buffer) = event.data().dyn_into::<js_sys::ArrayBuffer>() {
+                let array = Uint8Array::new(&buffer);
+                let mut bytes = vec![0; array.length() as usize];
+                array.copy_to(&mut bytes);
+                let _ = tx.unbounded_send(Ok(Bytes::from(bytes)));
+            }
+        })
+    };
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+    let on_error = {
+        let tx = tx.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            let _ = tx.unbounded_send(Err(Status::unavailable("grpc-web websocket error")));
+        })
+    };
+    socket.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+    let on_close = {
+        let tx = tx.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            // Closing the sender ends the response stream cleanly.
+            tx.close_channel();
+        })
+    };
+    socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+    // Send the header frame as soon as the socket opens, then forward each
+    // request message frame as it is produced by the tonic `Body`.
+    let header_frame = header_frame(&rpc);
+    let on_open = {
+        let socket = socket.clone();
+        let body = rpc.into_body();
+        Closure::<dyn FnMut()>::new(move || {
+            let _ = socket.send_with_str(&header_frame);
+            spawn_local(forward_body(socket.clone(), Box::pin(body)));
+        })
+    };
+    socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+
+    let guard = SocketGuard {
+        socket,
+        _on_open: on_open,
+        _on_message: on_message,
+        _on_error: on_error,
+        _on_close: on_close,
+    };
+
+    let body = GrpcWebCall::client_response(
+        WebSocketBody { rx, _guard: guard },
+        Encoding::None,
+        Compression::None,
+    );
+
+    Response::builder()
+        .status(200)
+        .body(BoxBody::new(body))
+        .map_err(|e| ClientError::Other(format!("An HTTP error ocurred: {}", e)))
+}
+
+// Rewrite an `http(s)` base URI to its `ws(s)` equivalent and append the RPC
+// path.
+fn websocket_url(base_uri: &str, path: &str) -> String {
+    let base = if let Some(rest) = base_uri.strip_prefix("https") {
+        format!("wss{}", rest)
+    } else if let Some(rest) = base_uri.strip_prefix("http") {
+        format!("ws{}", rest)
+    } else {
+        base_uri.to_string()
+    };
+    format!("{}{}", base, path)
+}
+
+// The first websocket message: the request path followed by its headers, in
+// HTTP/1 `name: value` form.
+fn header_frame(rpc: &http::Request<BoxBody>) -> String {
+    let mut lines = format!(":path: {}\r\n", rpc.uri());
+    for (name, value) in rpc.headers().iter() {
+        if let Ok(value) = value.to_str() {
+            lines.push_str(name.as_str());
+            lines.push_str(": ");
+            lines.push_str(value);
+            lines.push_str("\r\n");
+        }
+    }
+    lines
+}
+
+// Drain the request body one frame at a time, forwarding each as a `DATA`
+// frame and signalling `FINISH` once the body is exhausted.
+async fn forward_body(socket: WebSocket, mut body: Pin<Box<BoxBody>>) {
+    loop {
+        match futures::future::poll_fn(|cx| body.as_mut().poll_data(cx)).await {
+            Some(Ok(chunk)) => {
+                let mut frame = BytesMut::with_capacity(1 + chunk.len());
+                frame.extend_from_slice(&[FRAME_DATA]);
+                frame.extend_from_slice(&chunk);
+                if socket.send_with_u8_array(&frame).is_err() {
+                    return;
+                }
+            }
+            Some(Err(_)) => return,
+            None => break,
+        }
+    }
+    let _ = socket.send_with_u8_array(&[FRAME_FINISH]);
+}
+
+/// Keeps the socket and its event closures alive for as long as the response
+/// body is held, and closes the socket when the body is dropped so a cancelled
+/// stream tears down the connection.
+struct SocketGuard {
+    socket: WebSocket,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_error: Closure<dyn FnMut()>,
+    _on_close: Closure<dyn FnMut()>,
+}
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        // Closing an already-closed socket is a harmless no-op.
+        let _ = self.socket.close();
+    }
+}
+
+/// The `ReadableStreamBody` analogue for the websocket transport: it surfaces
+/// the binary frames delivered by the socket as a grpc-web framed body.
+struct WebSocketBody {
+    rx: UnboundedReceiver<Result<Bytes, Status>>,
+    _guard: SocketGuard,
+}
+
+impl Body for WebSocketBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        self.rx.poll_next_unpin(cx)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        false
+    }
+}
+
+// Mirrors the escape hatch in `lib.rs`: the websocket handles are not thread
+// safe, which is fine because wasm has no threads today.
+unsafe impl Sync for WebSocketBody {}
+unsafe impl Send for WebSocketBody {}
@@ -14,9 +14,10 @@ pub(crate) async fn fetch_with_request(request: web_sys::Request) -> Result<web_
     fetch.dyn_into().map_err(ClientError::FetchFailed)
 }
 
-pub(crate) fn post_init(_client: Client) -> RequestInit {
+pub(crate) fn post_init(_client: Client, signal: &web_sys::AbortSignal) -> RequestInit {
     let mut init = RequestInit::new();
     init.method("POST");
+    init.signal(Some(signal));
 
     init
 }
\ No newline at end of file